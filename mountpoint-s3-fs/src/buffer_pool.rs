@@ -1,14 +1,50 @@
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 
 use bytes::{Bytes, BytesMut};
 use tracing::{debug, trace};
 
+/// Process-wide count of buffers currently leased out across all [BufferPool]s, and the
+/// high-water mark of that count, kept up to date by every pool's lease/return calls so they can
+/// be emitted through `metrics::TARGET_NAME` logging alongside `rust_allocator.allocated_bytes`.
+static GLOBAL_LEASED_BUFFERS: AtomicUsize = AtomicUsize::new(0);
+static GLOBAL_HIGH_WATER_MARK: AtomicUsize = AtomicUsize::new(0);
+
+/// Process-wide number of buffers currently leased out across all buffer pools.
+pub fn global_leased_buffers() -> usize {
+    GLOBAL_LEASED_BUFFERS.load(Ordering::Acquire)
+}
+
+/// Process-wide high-water mark of [global_leased_buffers] since the process started.
+pub fn global_high_water_mark() -> usize {
+    GLOBAL_HIGH_WATER_MARK.load(Ordering::Acquire)
+}
+
 /// A simple buffer pool that allows reusing buffers to reduce memory fragmentation.
+///
+/// When created with a maximum number of buffers (see [new_bounded_buffer_pool]), the pool also
+/// bounds the number of buffers that can be leased out at any one time: once that many leases are
+/// outstanding, [BufferPool::get_buffer] parks the caller until one is returned.
 #[derive(Debug)]
 pub struct BufferPool {
-    buffers: Mutex<VecDeque<BytesMut>>,
+    inner: Mutex<BufferPoolInner>,
+    /// Notified whenever a buffer is returned to the pool, to wake up callers parked in `get_buffer`.
+    buffer_returned: Condvar,
     buffer_size: usize,
+    /// Maximum number of buffers that may be leased out at once. `None` means unbounded.
+    max_buffers: Option<usize>,
+    /// High-water mark of [BufferPoolInner::leased] since the pool was created.
+    high_water: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct BufferPoolInner {
+    buffers: VecDeque<BytesMut>,
+    /// Current number of buffers leased out to callers. Guarded by the same mutex as `buffers` so
+    /// checking `leased < max_buffers` and incrementing it happen atomically with respect to other
+    /// callers of `get_buffer`/`try_get_buffer`.
+    leased: usize,
 }
 
 #[derive(Debug)]
@@ -43,20 +79,59 @@ impl AsRef<[u8]> for LeasedBytesMut {
 
 impl BufferPool {
     /// Create a new buffer pool with the specified buffer size and optional maximum number of buffers.
-    fn new(buffer_size: usize) -> Self {
-        debug!("creating buffer pool with buffer_size={}", buffer_size);
+    fn new(buffer_size: usize, max_buffers: Option<usize>) -> Self {
+        debug!(
+            "creating buffer pool with buffer_size={}, max_buffers={:?}",
+            buffer_size, max_buffers
+        );
         Self {
-            buffers: Mutex::new(VecDeque::new()),
+            inner: Mutex::new(BufferPoolInner::default()),
+            buffer_returned: Condvar::new(),
             buffer_size,
+            max_buffers,
+            high_water: AtomicUsize::new(0),
         }
     }
 
     /// Get a buffer from the pool, or create a new one if none are available.
     ///
+    /// If the pool is bounded and already has `max_buffers` leases outstanding, this blocks the
+    /// calling thread until a [LeasedBytesMut] is dropped and its slot is returned.
+    ///
     /// Buffer will always be empty.
     pub fn get_buffer(self: &Arc<Self>) -> LeasedBytesMut {
-        let mut buffers = self.buffers.lock().unwrap();
-        let buffer = match buffers.pop_front() {
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            if let Some(max_buffers) = self.max_buffers {
+                if inner.leased >= max_buffers {
+                    trace!("buffer pool full, parking caller until a buffer is returned");
+                    inner = self.buffer_returned.wait(inner).unwrap();
+                    continue;
+                }
+            }
+            return self.lease_buffer(inner);
+        }
+    }
+
+    /// Try to get a buffer from the pool without blocking.
+    ///
+    /// Returns `None` if the pool is bounded and already has `max_buffers` leases outstanding.
+    pub fn try_get_buffer(self: &Arc<Self>) -> Option<LeasedBytesMut> {
+        let inner = self.inner.lock().unwrap();
+        if let Some(max_buffers) = self.max_buffers {
+            if inner.leased >= max_buffers {
+                return None;
+            }
+        }
+        Some(self.lease_buffer(inner))
+    }
+
+    /// Take (or allocate) a buffer and account for the new lease. Caller must have already
+    /// checked, while holding `inner`'s lock, that a lease is available; the check and the
+    /// increment below happen under the same critical section so concurrent callers can never
+    /// both observe a free slot and exceed `max_buffers`.
+    fn lease_buffer(self: &Arc<Self>, mut inner: std::sync::MutexGuard<'_, BufferPoolInner>) -> LeasedBytesMut {
+        let buffer = match inner.buffers.pop_front() {
             Some(mut buffer) => {
                 // Reset the buffer for reuse
                 buffer.clear();
@@ -68,21 +143,225 @@ impl BufferPool {
                 BytesMut::with_capacity(self.buffer_size)
             }
         };
+        inner.leased += 1;
+        let leased = inner.leased;
+        drop(inner);
+
+        self.high_water.fetch_max(leased, Ordering::AcqRel);
+        let global_leased = GLOBAL_LEASED_BUFFERS.fetch_add(1, Ordering::AcqRel) + 1;
+        GLOBAL_HIGH_WATER_MARK.fetch_max(global_leased, Ordering::AcqRel);
+
         LeasedBytesMut {
             buffer: Some(buffer),
-            pool: Arc::clone(&self),
+            pool: Arc::clone(self),
         }
     }
 
     /// Return a buffer to the pool for reuse.
     fn return_buffer(&self, buffer: BytesMut) {
-        let mut buffers = self.buffers.lock().unwrap();
-        trace!("Returning buffer to pool");
-        buffers.push_back(buffer);
+        let mut inner = self.inner.lock().unwrap();
+        let at_capacity = self
+            .max_buffers
+            .is_some_and(|max_buffers| inner.buffers.len() >= max_buffers);
+        if at_capacity {
+            // The pool is already holding as many buffers as we want to retain, so drop this one
+            // to actually reclaim its memory instead of letting the pool grow unbounded.
+            trace!("Pool at capacity, dropping returned buffer");
+        } else {
+            trace!("Returning buffer to pool");
+            inner.buffers.push_back(buffer);
+        }
+        inner.leased -= 1;
+        drop(inner);
+
+        GLOBAL_LEASED_BUFFERS.fetch_sub(1, Ordering::AcqRel);
+        self.buffer_returned.notify_one();
+    }
+
+    /// Number of buffers currently leased out to callers.
+    pub fn leased_buffers(&self) -> usize {
+        self.inner.lock().unwrap().leased
+    }
+
+    /// Largest number of buffers that have ever been leased out at once.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water.load(Ordering::Acquire)
     }
 }
 
 /// Create a new shared buffer pool with no maximum buffer limit.
 pub fn new_unbounded_buffer_pool(buffer_size: usize) -> Arc<BufferPool> {
-    Arc::new(BufferPool::new(buffer_size))
+    Arc::new(BufferPool::new(buffer_size, None))
+}
+
+/// Create a new shared buffer pool that retains at most `max_buffers` buffers and never allows
+/// more than `max_buffers` leases to be outstanding at once, parking callers in [BufferPool::get_buffer]
+/// until a buffer is returned.
+pub fn new_bounded_buffer_pool(buffer_size: usize, max_buffers: usize) -> Arc<BufferPool> {
+    Arc::new(BufferPool::new(buffer_size, Some(max_buffers)))
+}
+
+/// A buffer pool that keeps separate free lists for a handful of power-of-two size classes,
+/// so that requests of very different sizes (e.g. a small metadata read and a full part-sized
+/// read) don't compete for the same buffers or force over-allocation.
+///
+/// Unlike [BufferPool], buffers are not leased through an RAII guard: callers request a buffer
+/// with [SizeClassedBufferPool::get_buffer] and must explicitly hand it back with
+/// [SizeClassedBufferPool::return_buffer] once they're done with it.
+#[derive(Debug)]
+pub struct SizeClassedBufferPool {
+    /// Free lists for each size class, in ascending order of capacity.
+    classes: Vec<Mutex<VecDeque<BytesMut>>>,
+    /// Capacity of each size class, in ascending order, matching `classes` index-for-index.
+    class_sizes: Vec<usize>,
+}
+
+impl SizeClassedBufferPool {
+    /// Create a new pool whose largest size class is `max_size` (typically the configured S3 part
+    /// size), with power-of-two classes below it starting at `min_size`.
+    ///
+    /// `min_size` and `max_size` are rounded up to the nearest power of two.
+    pub fn new(min_size: usize, max_size: usize) -> Self {
+        let min_size = min_size.max(1).next_power_of_two();
+        let max_size = max_size.max(min_size).next_power_of_two();
+
+        let mut class_sizes = Vec::new();
+        let mut size = min_size;
+        while size < max_size {
+            class_sizes.push(size);
+            size *= 2;
+        }
+        class_sizes.push(max_size);
+
+        debug!("creating size-classed buffer pool with classes={:?}", class_sizes);
+        let classes = class_sizes.iter().map(|_| Mutex::new(VecDeque::new())).collect();
+        Self { classes, class_sizes }
+    }
+
+    /// Find the smallest size class that can hold `requested_len`, if any.
+    fn class_for(&self, requested_len: usize) -> Option<usize> {
+        self.class_sizes.iter().position(|&size| size >= requested_len)
+    }
+
+    /// Get an empty buffer with capacity for at least `requested_len` bytes, rounded up to the
+    /// nearest size class. If `requested_len` is larger than the biggest size class, a one-off
+    /// buffer is allocated that will not be retained when returned.
+    pub fn get_buffer(&self, requested_len: usize) -> BytesMut {
+        let Some(class) = self.class_for(requested_len) else {
+            trace!(
+                "requested_len={} exceeds largest size class, allocating one-off buffer",
+                requested_len
+            );
+            return BytesMut::with_capacity(requested_len);
+        };
+
+        let mut free_list = self.classes[class].lock().unwrap();
+        match free_list.pop_front() {
+            Some(mut buffer) => {
+                buffer.clear();
+                trace!("Reusing size class {} buffer from pool", self.class_sizes[class]);
+                buffer
+            }
+            None => {
+                trace!("Creating new size class {} buffer", self.class_sizes[class]);
+                BytesMut::with_capacity(self.class_sizes[class])
+            }
+        }
+    }
+
+    /// Return a buffer to the free list matching its capacity, if there is one. Buffers whose
+    /// capacity doesn't exactly match a size class (e.g. the one-off buffers handed out for
+    /// oversized requests) are dropped rather than retained.
+    pub fn return_buffer(&self, buffer: BytesMut) {
+        let Some(class) = self.class_sizes.iter().position(|&size| size == buffer.capacity()) else {
+            trace!("Dropping returned buffer that doesn't match a size class");
+            return;
+        };
+
+        trace!("Returning buffer to size class {} pool", self.class_sizes[class]);
+        self.classes[class].lock().unwrap().push_back(buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn unbounded_pool_has_no_limit() {
+        let pool = new_unbounded_buffer_pool(16);
+        let _a = pool.get_buffer();
+        let _b = pool.get_buffer();
+        assert_eq!(pool.leased_buffers(), 2);
+    }
+
+    #[test]
+    fn try_get_buffer_returns_none_when_bounded_pool_is_full() {
+        let pool = new_bounded_buffer_pool(16, 1);
+        let _a = pool.try_get_buffer().expect("first lease should succeed");
+        assert!(pool.try_get_buffer().is_none(), "pool should be full");
+    }
+
+    #[test]
+    fn returning_a_buffer_frees_up_a_lease() {
+        let pool = new_bounded_buffer_pool(16, 1);
+        let a = pool.get_buffer();
+        assert!(pool.try_get_buffer().is_none());
+        drop(a);
+        assert!(pool.try_get_buffer().is_some());
+    }
+
+    #[test]
+    fn bounded_pool_never_exceeds_max_buffers_under_contention() {
+        const MAX_BUFFERS: usize = 2;
+        const THREADS: usize = 8;
+        let pool = new_bounded_buffer_pool(16, MAX_BUFFERS);
+        let barrier = Arc::new(Barrier::new(THREADS));
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let pool = Arc::clone(&pool);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let _buffer = pool.get_buffer();
+                    assert!(pool.leased_buffers() <= MAX_BUFFERS);
+                    thread::sleep(Duration::from_millis(5));
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(pool.leased_buffers(), 0);
+        assert!(pool.high_water_mark() <= MAX_BUFFERS);
+    }
+
+    #[test]
+    fn size_classed_pool_rounds_up_to_class() {
+        let pool = SizeClassedBufferPool::new(64 * 1024, 1024 * 1024);
+        let buf = pool.get_buffer(100 * 1024);
+        assert_eq!(buf.capacity(), 128 * 1024);
+    }
+
+    #[test]
+    fn size_classed_pool_reuses_returned_buffer_of_same_class() {
+        let pool = SizeClassedBufferPool::new(64 * 1024, 1024 * 1024);
+        let buf = pool.get_buffer(64 * 1024);
+        let capacity = buf.capacity();
+        pool.return_buffer(buf);
+        let reused = pool.get_buffer(64 * 1024);
+        assert_eq!(reused.capacity(), capacity);
+    }
+
+    #[test]
+    fn size_classed_pool_allocates_one_off_buffer_when_oversized() {
+        let pool = SizeClassedBufferPool::new(64 * 1024, 256 * 1024);
+        let buf = pool.get_buffer(10 * 1024 * 1024);
+        assert_eq!(buf.capacity(), 10 * 1024 * 1024);
+    }
 }
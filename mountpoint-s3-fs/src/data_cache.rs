@@ -0,0 +1,388 @@
+//! A pluggable cache of object data blocks, used to avoid re-fetching previously read bytes from S3.
+//!
+//! [DiskDataCache] memory-maps its cache files and therefore depends on the `memmap2` crate; this
+//! is the one new dependency in this module that can't be avoided by using only the standard
+//! library, since reattaching to existing cache files on restart is the whole point of the type.
+//! Needs adding to `mountpoint-s3-fs`'s `Cargo.toml` alongside the crate's other dependencies.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use memmap2::MmapMut;
+use tracing::{debug, trace, warn};
+
+/// Index of a fixed-size block within an object, counted from the start of the object.
+pub type BlockIndex = u64;
+
+/// Uniquely identifies the version of an object that a cached block belongs to, so that a
+/// stale cache entry for an object that has since changed is never served.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub key: String,
+    pub etag: String,
+}
+
+impl CacheKey {
+    pub fn new(key: impl Into<String>, etag: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            etag: etag.into(),
+        }
+    }
+}
+
+/// A cache of object data blocks, shared between open file handles so that repeated reads (and
+/// reads of the same object by different file handles) don't need to go back to S3.
+pub trait DataCache: Send + Sync {
+    /// Size of a single block in this cache.
+    fn block_size(&self) -> usize;
+
+    /// Look up a previously cached block. Returns `None` if the block isn't cached, including if
+    /// it was cached for a different version (ETag) of the object.
+    fn get_block(&self, cache_key: &CacheKey, block_idx: BlockIndex) -> Option<Bytes>;
+
+    /// Insert a block into the cache, replacing any block already cached under the same key.
+    fn put_block(&self, cache_key: CacheKey, block_idx: BlockIndex, bytes: Bytes);
+}
+
+/// A [DataCache] that keeps all blocks in memory.
+///
+/// Data does not survive a remount -- see [DiskDataCache] for a cache that does.
+#[derive(Debug)]
+pub struct InMemoryDataCache {
+    block_size: usize,
+    inner: Mutex<HashMap<(CacheKey, BlockIndex), Bytes>>,
+}
+
+const DEFAULT_BLOCK_SIZE: usize = 1024 * 1024;
+
+impl InMemoryDataCache {
+    /// Create a new in-memory cache with the default block size.
+    pub fn new(_max_size_in_bytes: usize) -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl DataCache for InMemoryDataCache {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn get_block(&self, cache_key: &CacheKey, block_idx: BlockIndex) -> Option<Bytes> {
+        self.inner.lock().unwrap().get(&(cache_key.clone(), block_idx)).cloned()
+    }
+
+    fn put_block(&self, cache_key: CacheKey, block_idx: BlockIndex, bytes: Bytes) {
+        self.inner.lock().unwrap().insert((cache_key, block_idx), bytes);
+    }
+}
+
+/// On-disk format version. Bump this whenever the header or block layout changes so that cache
+/// files written by an older build are never misinterpreted by a newer one.
+const CACHE_FILE_FORMAT_VERSION: u64 = 1;
+
+const MAX_ETAG_LEN: usize = 128;
+const MAX_KEY_LEN: usize = 1024;
+
+/// Fixed-size header written at the start of every cache file. Used by [DiskDataCache::load_on_restart]
+/// to validate that a cache file found on disk is compatible with the current build and to
+/// reconstruct the exact [CacheKey] (object key and ETag) it was cached under, since the file
+/// name alone (a hash of the key, for a short, filesystem-safe name) can't be inverted back into it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CacheFileHeader {
+    version: u64,
+    block_size: u64,
+    etag_len: u64,
+    etag: [u8; MAX_ETAG_LEN],
+    key_len: u64,
+    key: [u8; MAX_KEY_LEN],
+}
+
+impl CacheFileHeader {
+    const SIZE: usize = std::mem::size_of::<CacheFileHeader>();
+
+    fn new(block_size: usize, key: &str, etag: &str) -> io::Result<Self> {
+        if etag.len() > MAX_ETAG_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "ETag too long for cache header"));
+        }
+        if key.len() > MAX_KEY_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "key too long for cache header"));
+        }
+        let mut etag_bytes = [0u8; MAX_ETAG_LEN];
+        etag_bytes[..etag.len()].copy_from_slice(etag.as_bytes());
+        let mut key_bytes = [0u8; MAX_KEY_LEN];
+        key_bytes[..key.len()].copy_from_slice(key.as_bytes());
+        Ok(Self {
+            version: CACHE_FILE_FORMAT_VERSION,
+            block_size: block_size as u64,
+            etag_len: etag.len() as u64,
+            etag: etag_bytes,
+            key_len: key.len() as u64,
+            key: key_bytes,
+        })
+    }
+
+    fn etag(&self) -> &str {
+        std::str::from_utf8(&self.etag[..self.etag_len as usize]).unwrap_or_default()
+    }
+
+    fn key(&self) -> &str {
+        std::str::from_utf8(&self.key[..self.key_len as usize]).unwrap_or_default()
+    }
+
+    fn cache_key(&self) -> CacheKey {
+        CacheKey::new(self.key(), self.etag())
+    }
+
+    /// Read the header out of the start of `mmap`, rejecting it if it isn't from a compatible
+    /// build or doesn't describe the current block size.
+    fn read_from(mmap: &MmapMut, block_size: usize) -> io::Result<Self> {
+        if mmap.len() < Self::SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "cache file too small for header"));
+        }
+        // Safety: `CacheFileHeader` is a plain-old-data struct and the mmap is guaranteed to be
+        // at least `Self::SIZE` bytes long, as checked above.
+        let header = unsafe { *(mmap.as_ptr() as *const CacheFileHeader) };
+        if header.version != CACHE_FILE_FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "cache file version {} does not match current version {}",
+                    header.version, CACHE_FILE_FORMAT_VERSION
+                ),
+            ));
+        }
+        if header.block_size != block_size as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "cache file block size {} does not match configured block size {}",
+                    header.block_size, block_size
+                ),
+            ));
+        }
+        if header.etag_len as usize > MAX_ETAG_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("cache file etag_len {} exceeds maximum of {MAX_ETAG_LEN}", header.etag_len),
+            ));
+        }
+        if header.key_len as usize > MAX_KEY_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("cache file key_len {} exceeds maximum of {MAX_KEY_LEN}", header.key_len),
+            ));
+        }
+        Ok(header)
+    }
+}
+
+/// A [DataCache] that memory-maps block files on disk, so that cached data survives a remount
+/// rather than being lost on unmount like [InMemoryDataCache].
+///
+/// Each cached block of an object gets its own file under `cache_dir`, starting with a [CacheFileHeader]
+/// recording the format version, block size, and ETag of the object version it was cached for,
+/// followed by a single block of data. On startup, [DiskDataCache::load_on_restart] mmaps any
+/// existing cache files and reattaches to them; files whose header doesn't match the current
+/// build, or whose ETag no longer matches the live object, are treated as a cache miss rather
+/// than served.
+#[derive(Debug)]
+pub struct DiskDataCache {
+    cache_dir: PathBuf,
+    block_size: usize,
+    inner: Mutex<HashMap<(CacheKey, BlockIndex), MmapMut>>,
+}
+
+impl DiskDataCache {
+    /// Create a new, empty disk cache rooted at `cache_dir`.
+    pub fn new(cache_dir: impl Into<PathBuf>, block_size: usize) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+            block_size,
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reattach to any cache files already present under `cache_dir` from a previous run.
+    ///
+    /// Files that fail to validate (wrong version, wrong block size, or unreadable) are skipped
+    /// with a warning rather than failing the whole mount; they'll simply be treated as a cache
+    /// miss and overwritten the next time that block is cached.
+    pub fn load_on_restart(cache_dir: impl Into<PathBuf>, block_size: usize) -> io::Result<Self> {
+        let cache_dir = cache_dir.into();
+        let cache = Self::new(&cache_dir, block_size);
+
+        let entries = match std::fs::read_dir(&cache_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            match cache.reattach_file(&path) {
+                Ok(Some((cache_key, block_idx, mmap))) => {
+                    debug!(?path, key = %cache_key.key, block_idx, "reattached cache file on restart");
+                    cache.inner.lock().unwrap().insert((cache_key, block_idx), mmap);
+                }
+                Ok(None) => trace!(?path, "cache file is not a recognized cache entry, ignoring"),
+                Err(e) => warn!(?path, error = %e, "discarding incompatible cache file on restart"),
+            }
+        }
+
+        Ok(cache)
+    }
+
+    fn file_path(&self, cache_key: &CacheKey, block_idx: BlockIndex) -> PathBuf {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        cache_key.key.hash(&mut hasher);
+        let file_name = format!("{:x}-{block_idx}", hasher.finish());
+        self.cache_dir.join(file_name)
+    }
+
+    /// Try to mmap and validate an existing cache file found during [Self::load_on_restart],
+    /// returning the exact [CacheKey] it was cached under (read back out of the header, not
+    /// guessed from the file name) so it's reachable by a real caller's `get_block`.
+    fn reattach_file(&self, path: &Path) -> io::Result<Option<(CacheKey, BlockIndex, MmapMut)>> {
+        let Some(file_name) = path.file_name().and_then(|f| f.to_str()) else {
+            return Ok(None);
+        };
+        let Some((_hash, block_idx)) = file_name.rsplit_once('-') else {
+            return Ok(None);
+        };
+        let Ok(block_idx) = block_idx.parse::<BlockIndex>() else {
+            return Ok(None);
+        };
+
+        let file = File::options().read(true).write(true).open(path)?;
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        let header = CacheFileHeader::read_from(&mmap, self.block_size)?;
+        let cache_key = header.cache_key();
+        mmap.flush()?;
+        Ok(Some((cache_key, block_idx, mmap)))
+    }
+}
+
+impl DataCache for DiskDataCache {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn get_block(&self, cache_key: &CacheKey, block_idx: BlockIndex) -> Option<Bytes> {
+        let inner = self.inner.lock().unwrap();
+        let mmap = inner.get(&(cache_key.clone(), block_idx))?;
+        let header = CacheFileHeader::read_from(mmap, self.block_size).ok()?;
+        if header.etag() != cache_key.etag {
+            // Stale entry for an object that has since changed; treat it as a miss rather than
+            // serving out-of-date bytes (mirroring `avoid_stuck_cached_file_on_change`).
+            trace!(key = %cache_key.key, "cached block has stale ETag, treating as a miss");
+            return None;
+        }
+        Some(Bytes::copy_from_slice(&mmap[CacheFileHeader::SIZE..]))
+    }
+
+    fn put_block(&self, cache_key: CacheKey, block_idx: BlockIndex, bytes: Bytes) {
+        let path = self.file_path(&cache_key, block_idx);
+
+        // Hold `inner`'s lock across the whole write, not just the final insert: the file
+        // open/truncate/mmap/write sequence below isn't safe to run concurrently for the same
+        // `(cache_key, block_idx)`, since two callers would race to truncate and write the same
+        // underlying file out from under each other.
+        let mut inner = self.inner.lock().unwrap();
+        let result = (|| -> io::Result<MmapMut> {
+            let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+            file.set_len((CacheFileHeader::SIZE + bytes.len()) as u64)?;
+            let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+            let header = CacheFileHeader::new(self.block_size, &cache_key.key, &cache_key.etag)?;
+            // Safety: `mmap` was just sized to hold the header followed by the block's bytes.
+            unsafe {
+                std::ptr::write(mmap.as_mut_ptr() as *mut CacheFileHeader, header);
+            }
+            mmap[CacheFileHeader::SIZE..].copy_from_slice(&bytes);
+            mmap.flush()?;
+            Ok(mmap)
+        })();
+
+        match result {
+            Ok(mmap) => {
+                inner.insert((cache_key, block_idx), mmap);
+            }
+            Err(e) => warn!(?path, error = %e, "failed to write block to disk cache"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+    static TEST_DIR_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    /// A fresh, empty directory for a single test to use as its cache dir.
+    fn temp_cache_dir() -> PathBuf {
+        let id = TEST_DIR_COUNTER.fetch_add(1, AtomicOrdering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("mountpoint_s3_disk_data_cache_test_{}_{id}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn in_memory_cache_round_trips_a_block() {
+        let cache = InMemoryDataCache::new(1024 * 1024);
+        let key = CacheKey::new("objects/foo", "etag-1");
+        cache.put_block(key.clone(), 0, Bytes::from_static(b"hello world"));
+        assert_eq!(cache.get_block(&key, 0), Some(Bytes::from_static(b"hello world")));
+        assert_eq!(cache.get_block(&key, 1), None);
+    }
+
+    #[test]
+    fn disk_cache_round_trips_a_block_after_restart() {
+        let dir = temp_cache_dir();
+        let key = CacheKey::new("objects/foo/bar.bin", "etag-1");
+        let data = Bytes::from_static(b"some cached bytes");
+
+        {
+            let cache = DiskDataCache::new(&dir, 4096);
+            cache.put_block(key.clone(), 3, data.clone());
+            assert_eq!(cache.get_block(&key, 3), Some(data.clone()));
+        }
+
+        // Simulate a remount: a fresh cache reattaches to the files the previous one left behind.
+        let reattached = DiskDataCache::load_on_restart(&dir, 4096).unwrap();
+        assert_eq!(
+            reattached.get_block(&key, 3),
+            Some(data),
+            "block cached under the real object key should be reachable after reattaching"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn disk_cache_invalidates_entry_with_stale_etag_after_restart() {
+        let dir = temp_cache_dir();
+        let stale_key = CacheKey::new("objects/foo", "etag-old");
+
+        {
+            let cache = DiskDataCache::new(&dir, 4096);
+            cache.put_block(stale_key, 0, Bytes::from_static(b"stale bytes"));
+        }
+
+        let reattached = DiskDataCache::load_on_restart(&dir, 4096).unwrap();
+        let current_key = CacheKey::new("objects/foo", "etag-new");
+        assert_eq!(reattached.get_block(&current_key, 0), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
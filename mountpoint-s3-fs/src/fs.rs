@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+/// How aggressively a file's metadata and data should be cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Don't retain this object's data or metadata at all: bypass the [crate::buffer_pool::BufferPool]
+    /// retention and the data cache, streaming reads straight through to S3.
+    None,
+    /// Cache only lookup/attribute metadata; don't retain or prefetch file data blocks.
+    Metadata,
+    /// Cache metadata and aggressively prefetch and retain file data blocks.
+    #[default]
+    Full,
+}
+
+/// Matches a key against a glob pattern supporting `*` (any run of characters, including none)
+/// and `?` (exactly one character). There's no need to pull in a full glob library for the
+/// handful of patterns a cache policy config holds.
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    // Standard iterative wildcard matcher: track the last `*` seen in the pattern (if any) and
+    // the position in `key` it matched from, so on a mismatch we can backtrack to it and retry
+    // having it consume one more character instead of needing recursion/DP.
+    let (mut p, mut k) = (0, 0);
+    let (mut star_p, mut star_k) = (None, 0);
+
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star_p = Some(p);
+            star_k = k;
+            p += 1;
+        } else if let Some(sp) = star_p {
+            p = sp + 1;
+            star_k += 1;
+            k = star_k;
+        } else {
+            return false;
+        }
+    }
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+/// Maps object keys to a [CachePolicy] via glob patterns, so e.g. huge write-once objects can be
+/// excluded from the cache while small, frequently-read files stay fully resident.
+///
+/// Patterns are matched in the order they were configured, and the first match wins; keys that
+/// don't match anything fall back to [CachePolicy::Full].
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicyConfig {
+    patterns: Vec<(String, CachePolicy)>,
+}
+
+impl CachePolicyConfig {
+    /// Build a config from an ordered list of `(glob pattern, policy)` pairs.
+    pub fn new(patterns: impl IntoIterator<Item = (String, CachePolicy)>) -> Self {
+        Self {
+            patterns: patterns.into_iter().collect(),
+        }
+    }
+
+    /// The [CachePolicy] to use for `key`, matching the first configured pattern in order, or
+    /// [CachePolicy::Full] if nothing matches.
+    pub fn policy_for(&self, key: &str) -> CachePolicy {
+        self.patterns
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern.as_bytes(), key.as_bytes()))
+            .map(|(_, policy)| *policy)
+            .unwrap_or_default()
+    }
+}
+
+/// Configuration for how the filesystem caches object metadata and data.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub serve_lookup_from_cache: bool,
+    pub dir_ttl: Duration,
+    pub file_ttl: Duration,
+    /// Per-object/per-prefix override of [CachePolicy], consulted when a file is opened.
+    pub cache_policy: CachePolicyConfig,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            serve_lookup_from_cache: false,
+            dir_ttl: Duration::ZERO,
+            file_ttl: Duration::ZERO,
+            cache_policy: CachePolicyConfig::default(),
+        }
+    }
+}
+
+impl CacheConfig {
+    /// The [CachePolicy] to apply when opening `key`, consulted by the FUSE layer at open time to
+    /// decide whether to bypass the cache entirely, cache metadata only, or cache and prefetch
+    /// aggressively.
+    pub fn policy_for_open(&self, key: &str) -> CachePolicy {
+        self.cache_policy.policy_for(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_matching_pattern_wins() {
+        let config = CachePolicyConfig::new([
+            ("logs/*".to_string(), CachePolicy::None),
+            ("logs/important.log".to_string(), CachePolicy::Full),
+        ]);
+        assert_eq!(config.policy_for("logs/important.log"), CachePolicy::None);
+    }
+
+    #[test]
+    fn unmatched_key_defaults_to_full() {
+        let config = CachePolicyConfig::new([("archive/*".to_string(), CachePolicy::None)]);
+        assert_eq!(config.policy_for("hot/data.bin"), CachePolicy::Full);
+    }
+
+    #[test]
+    fn wildcard_patterns_match_expected_keys() {
+        let config = CachePolicyConfig::new([("data/*.parquet".to_string(), CachePolicy::Metadata)]);
+        assert_eq!(config.policy_for("data/part-0001.parquet"), CachePolicy::Metadata);
+        assert_eq!(config.policy_for("data/part-0001.csv"), CachePolicy::Full);
+    }
+}
@@ -0,0 +1,239 @@
+//! Keeps a bounded window of S3 ranged-GET futures in flight ahead of a sequential reader, so the
+//! pipe to S3 doesn't sit idle between the consumer's requests.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::task::JoinHandle;
+use tracing::trace;
+
+use crate::buffer_pool::{BufferPool, LeasedBytesMut};
+
+/// Index of a fixed-size block within an object, counted from the start of the object.
+pub type BlockIndex = u64;
+
+/// Default number of blocks to keep prefetched ahead of the reader's current position.
+pub const DEFAULT_PREFETCH_WINDOW: usize = 4;
+
+/// Default number of entries the prefetcher's LRU will hold across all objects. Sized larger than
+/// the window so that a handful of concurrently open files can each keep their window prefetched.
+pub const DEFAULT_PREFETCH_CAPACITY: usize = DEFAULT_PREFETCH_WINDOW * 4;
+
+#[derive(Debug)]
+pub enum PrefetchError {
+    GetObjectFailed(String),
+}
+
+impl std::fmt::Display for PrefetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefetchError::GetObjectFailed(msg) => write!(f, "failed to get object data from S3: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for PrefetchError {}
+
+/// Fetches a single block of an object's data from S3. Implemented by the S3 client wiring; kept
+/// as a trait so the prefetcher itself doesn't need to know about request signing, retries, etc.
+pub trait GetBlock: Send + Sync + 'static {
+    type Future: Future<Output = Result<LeasedBytesMut, PrefetchError>> + Send + 'static;
+
+    /// Fetch `block_idx` of `object_key`, filling `buffer` with its bytes.
+    fn get_block(&self, object_key: &str, block_idx: BlockIndex, buffer: LeasedBytesMut) -> Self::Future;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BlockKey {
+    object_key: String,
+    block_idx: BlockIndex,
+}
+
+/// Keeps a fixed number of [GetBlock] futures in flight ahead of the consumer's current read
+/// offset, in a small fixed-capacity LRU keyed by `(object_key, block_index)`. Futures are spawned
+/// directly into pooled [LeasedBytesMut] buffers so completed prefetches can be handed to the FUSE
+/// reply without an extra copy.
+pub struct Prefetcher<G: GetBlock> {
+    client: Arc<G>,
+    buffer_pool: Arc<BufferPool>,
+    window: usize,
+    /// Maximum number of in-flight/completed prefetches retained at once, across all objects.
+    capacity: usize,
+    state: Mutex<PrefetcherState>,
+}
+
+struct PrefetcherState {
+    tasks: HashMap<BlockKey, JoinHandle<Result<LeasedBytesMut, PrefetchError>>>,
+    /// Entries in LRU order, oldest (least-recently-inserted) first. Used both to evict stale
+    /// entries left behind by a seek, and to enforce the fixed capacity across all objects.
+    order: VecDeque<BlockKey>,
+}
+
+impl<G: GetBlock> Prefetcher<G> {
+    pub fn new(client: G, buffer_pool: Arc<BufferPool>, window: usize) -> Self {
+        Self::with_capacity(client, buffer_pool, window, DEFAULT_PREFETCH_CAPACITY.max(window))
+    }
+
+    pub fn with_capacity(client: G, buffer_pool: Arc<BufferPool>, window: usize, capacity: usize) -> Self {
+        Self {
+            client: Arc::new(client),
+            buffer_pool,
+            window,
+            capacity,
+            state: Mutex::new(PrefetcherState {
+                tasks: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Called when the consumer reads `block_idx` of `object_key`: ensures futures for
+    /// `block_idx + 1 ..= block_idx + window` are in flight, evicts (cancelling) any in-flight or
+    /// completed prefetches for this object that fall outside of the new window because the
+    /// reader seeked away, and enforces the fixed capacity across all objects by evicting the
+    /// least-recently-inserted entries first.
+    pub fn on_read(&self, object_key: &str, block_idx: BlockIndex) {
+        let wanted: Vec<BlockIndex> = (1..=self.window as BlockIndex).map(|i| block_idx + i).collect();
+
+        let mut state = self.state.lock().unwrap();
+
+        // First pass: figure out which entries for this object are now stale, without holding
+        // more than one borrow of `state` at a time (`VecDeque::retain`'s closure can't also
+        // mutate `state.tasks`).
+        let stale: Vec<BlockKey> = state
+            .order
+            .iter()
+            .filter(|key| key.object_key == object_key && !wanted.contains(&key.block_idx))
+            .cloned()
+            .collect();
+        for key in &stale {
+            if let Some(task) = state.tasks.remove(key) {
+                trace!(key = %key.object_key, block_idx = key.block_idx, "evicting stale prefetch");
+                task.abort();
+            }
+        }
+        state.order.retain(|key| !stale.contains(key));
+
+        for block_idx in wanted {
+            let key = BlockKey {
+                object_key: object_key.to_owned(),
+                block_idx,
+            };
+            if state.tasks.contains_key(&key) {
+                continue;
+            }
+
+            // Use the non-blocking variant here: `state`'s lock is held for the rest of this
+            // function, and it's also needed to free up a buffer (via `take_block` or a future
+            // `on_read` eviction), so blocking on a saturated pool while holding it would deadlock
+            // forever. Skipping this block just means it isn't prefetched; the consumer's own read
+            // will fetch it directly once it gets there.
+            let Some(buffer) = self.buffer_pool.try_get_buffer() else {
+                trace!(object_key, block_idx, "buffer pool exhausted, skipping prefetch");
+                continue;
+            };
+            let client = Arc::clone(&self.client);
+            let fetch_key = key.clone();
+            let task = tokio::spawn(async move { client.get_block(&fetch_key.object_key, fetch_key.block_idx, buffer).await });
+
+            trace!(object_key, block_idx, "spawned prefetch");
+            state.tasks.insert(key.clone(), task);
+            state.order.push_back(key);
+        }
+
+        // Enforce the fixed capacity: evict the least-recently-inserted entries, regardless of
+        // which object they belong to, so memory and in-flight requests stay bounded even across
+        // many open files.
+        while state.order.len() > self.capacity {
+            let Some(oldest) = state.order.pop_front() else { break };
+            if let Some(task) = state.tasks.remove(&oldest) {
+                trace!(key = %oldest.object_key, block_idx = oldest.block_idx, "evicting prefetch over capacity");
+                task.abort();
+            }
+        }
+    }
+
+    /// Take the pending or completed future for `block_idx` of `object_key`, if one is in flight,
+    /// removing it from the window so it's only consumed once.
+    pub fn take_block(&self, object_key: &str, block_idx: BlockIndex) -> Option<JoinHandle<Result<LeasedBytesMut, PrefetchError>>> {
+        let key = BlockKey {
+            object_key: object_key.to_owned(),
+            block_idx,
+        };
+        let mut state = self.state.lock().unwrap();
+        let task = state.tasks.remove(&key)?;
+        state.order.retain(|k| k != &key);
+        Some(task)
+    }
+
+    /// Number of prefetch entries (in-flight or completed) currently retained.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer_pool::new_unbounded_buffer_pool;
+    use std::future::{ready, Ready};
+
+    struct FakeClient;
+
+    impl GetBlock for FakeClient {
+        type Future = Ready<Result<LeasedBytesMut, PrefetchError>>;
+
+        fn get_block(&self, _object_key: &str, _block_idx: BlockIndex, buffer: LeasedBytesMut) -> Self::Future {
+            ready(Ok(buffer))
+        }
+    }
+
+    #[tokio::test]
+    async fn on_read_spawns_futures_for_the_configured_window() {
+        let buffer_pool = new_unbounded_buffer_pool(16);
+        let prefetcher = Prefetcher::new(FakeClient, buffer_pool, 4);
+
+        prefetcher.on_read("object", 0);
+        assert_eq!(prefetcher.len(), 4);
+        for block_idx in 1..=4 {
+            assert!(prefetcher.take_block("object", block_idx).is_some());
+        }
+        assert!(prefetcher.is_empty());
+    }
+
+    #[tokio::test]
+    async fn seeking_away_evicts_stale_entries_for_the_same_object() {
+        let buffer_pool = new_unbounded_buffer_pool(16);
+        let prefetcher = Prefetcher::new(FakeClient, buffer_pool, 2);
+
+        prefetcher.on_read("object", 0);
+        assert_eq!(prefetcher.len(), 2); // blocks 1, 2 prefetched
+
+        // Seek far away: blocks 1 and 2 are no longer within the window and should be evicted.
+        prefetcher.on_read("object", 100);
+        assert_eq!(prefetcher.len(), 2); // blocks 101, 102 prefetched instead
+        assert!(prefetcher.take_block("object", 1).is_none());
+        assert!(prefetcher.take_block("object", 2).is_none());
+        assert!(prefetcher.take_block("object", 101).is_some());
+    }
+
+    #[tokio::test]
+    async fn capacity_is_enforced_across_multiple_objects() {
+        let buffer_pool = new_unbounded_buffer_pool(16);
+        let prefetcher = Prefetcher::with_capacity(FakeClient, buffer_pool, 2, 3);
+
+        prefetcher.on_read("a", 0); // inserts a:1, a:2 (len 2)
+        prefetcher.on_read("b", 0); // wants b:1, b:2; capacity 3 evicts the oldest entry, a:1
+
+        assert_eq!(prefetcher.len(), 3);
+        assert!(
+            prefetcher.take_block("a", 1).is_none(),
+            "oldest entry should have been evicted once capacity was exceeded"
+        );
+    }
+}
@@ -13,6 +13,16 @@ fn main() -> anyhow::Result<()> {
     std::thread::spawn(|| loop {
         std::thread::sleep(Duration::from_millis(1000));
         tracing::info!(target: mountpoint_s3_fs::metrics::TARGET_NAME, "rust_allocator.allocated_bytes: {}", &ALLOCATOR.allocated());
+        tracing::info!(
+            target: mountpoint_s3_fs::metrics::TARGET_NAME,
+            "buffer_pool.leased_buffers: {}",
+            mountpoint_s3_fs::buffer_pool::global_leased_buffers()
+        );
+        tracing::info!(
+            target: mountpoint_s3_fs::metrics::TARGET_NAME,
+            "buffer_pool.high_water_mark: {}",
+            mountpoint_s3_fs::buffer_pool::global_high_water_mark()
+        );
     });
 
     mountpoint_s3::run(create_s3_client, cli_args)